@@ -6,7 +6,7 @@ extern crate mime;
 use std::path::PathBuf;
 
 use actix_web::{http, server, App, AsyncResponder, HttpMessage, HttpRequest, HttpResponse, State};
-use form_data::{handle_multipart, Error, Field, FilenameGenerator, Form};
+use form_data::{disk_sink, handle_multipart, Error, Field, FilenameGenerator, Form};
 use futures::Future;
 
 struct Gen;
@@ -41,7 +41,7 @@ fn main() {
                 .field("Two", Field::float())
                 .finalize(),
         )
-        .field("files", Field::array(Field::file(Gen)));
+        .field("files", Field::array(Field::file(disk_sink(Gen)).finalize()));
 
     println!("{:?}", form);
 
@@ -111,7 +111,7 @@ fn main() {
                 .field("Two", Field::float())
                 .finalize(),
         )
-        .field("files", Field::array(Field::file(Gen::new())));
+        .field("files", Field::array(Field::file(disk_sink(Gen::new())).finalize()));
 
     info!("{:?}", form);
 
@@ -0,0 +1,112 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+//! # Form Data Derive
+//! `#[derive(FromMultipart)]` for `form_data::Value`.
+//!
+//! Generates an implementation of `form_data::FromMultipart` for a struct, mapping each field
+//! name (or its `#[multipart(rename = "...")]` override) to a key of the consolidated
+//! `Value::Map` produced by `handle_multipart`.
+//!
+//! ```ignore
+//! #[derive(FromMultipart)]
+//! struct MyUpload {
+//!     title: String,
+//!     age: i64,
+//!     #[multipart(rename = "profile-picture")]
+//!     avatar: SavedFile,
+//!     tags: Vec<String>,
+//! }
+//! ```
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use syn::{Data, DeriveInput, Fields};
+
+#[proc_macro_derive(FromMultipart, attributes(multipart))]
+pub fn derive_from_multipart(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).expect("Failed to parse derive input");
+
+    let name = input.ident;
+
+    let fields = match input.data {
+        Data::Struct(data) => match data.fields {
+            Fields::Named(fields) => fields.named,
+            _ => panic!("FromMultipart only supports structs with named fields"),
+        },
+        _ => panic!("FromMultipart can only be derived for structs"),
+    };
+
+    let extractions = fields.iter().map(|field| {
+        let ident = field.ident.clone().expect("Named field without ident");
+        let key = rename_for(field).unwrap_or_else(|| ident.to_string());
+        let ty = &field.ty;
+
+        quote! {
+            #ident: <#ty as ::form_data::FromMultipartField>::from_field(&mut map, #key)?,
+        }
+    });
+
+    let expanded = quote! {
+        impl ::form_data::FromMultipart for #name {
+            fn from_multipart(
+                value: ::form_data::Value<::form_data::SavedFile>,
+            ) -> ::std::result::Result<Self, ::form_data::FromMultipartError> {
+                let mut map = match value {
+                    ::form_data::Value::Map(map) => map,
+                    _ => return ::std::result::Result::Err(::form_data::FromMultipartError::NotAMap),
+                };
+
+                ::std::result::Result::Ok(#name {
+                    #(#extractions)*
+                })
+            }
+        }
+    };
+
+    expanded.into()
+}
+
+/// Look for `#[multipart(rename = "...")]` on a field and return the override, if any.
+fn rename_for(field: &syn::Field) -> Option<String> {
+    field.attrs.iter().filter(|attr| attr.path.is_ident("multipart")).find_map(|attr| {
+        let list = match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list,
+            _ => return None,
+        };
+
+        list.nested.into_iter().find_map(|nested| match nested {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) => {
+                if nv.path.is_ident("rename") {
+                    match nv.lit {
+                        syn::Lit::Str(s) => Some(s.value()),
+                        _ => None,
+                    }
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        })
+    })
+}
@@ -0,0 +1,371 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{collections::HashMap, fmt, vec};
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer,
+                 MapAccess, SeqAccess, Visitor};
+
+use types::{SavedFile, Value};
+
+/// The error produced when a `Value` doesn't match the shape `T` expects.
+#[derive(Debug, Fail)]
+pub enum DeserializeError {
+    #[fail(display = "{}", _0)]
+    Message(String),
+}
+
+// `#[derive(Fail)]` gives us `Display`, but `serde::de::Error` also requires
+// `std::error::Error`, which `Fail` doesn't imply - so it's spelled out by hand.
+impl ::std::error::Error for DeserializeError {}
+
+impl de::Error for DeserializeError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: fmt::Display,
+    {
+        DeserializeError::Message(msg.to_string())
+    }
+}
+
+impl Value<SavedFile> {
+    /// Deserialize this `Value` into `T`, via `serde`.
+    ///
+    /// `Map`/`Array` drive the matching `Visitor` methods directly; `File` deserializes as its
+    /// `stored_as` path when `T` (or one of its fields) expects a string, or as a two-field
+    /// `{ filename, stored_as }` struct otherwise.
+    pub fn deserialize_into<T>(self) -> Result<T, DeserializeError>
+    where
+        T: DeserializeOwned,
+    {
+        T::deserialize(self)
+    }
+}
+
+struct MapDeserializer {
+    iter: ::std::collections::hash_map::IntoIter<String, Value<SavedFile>>,
+    value: Option<Value<SavedFile>>,
+}
+
+impl MapDeserializer {
+    fn new(map: HashMap<String, Value<SavedFile>>) -> Self {
+        MapDeserializer {
+            iter: map.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+struct SeqDeserializer {
+    iter: vec::IntoIter<Value<SavedFile>>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = DeserializeError;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, Self::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.iter.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        }
+    }
+}
+
+/// Presents a `SavedFile` as a two-entry `{ filename, stored_as }` map for `deserialize_any`.
+struct FileMapDeserializer {
+    fields: vec::IntoIter<(&'static str, Value<SavedFile>)>,
+    value: Option<Value<SavedFile>>,
+}
+
+impl FileMapDeserializer {
+    fn new(file: SavedFile) -> Self {
+        let fields = vec![
+            ("filename", Value::Text(file.filename)),
+            (
+                "stored_as",
+                Value::Text(file.stored_as.to_string_lossy().into_owned()),
+            ),
+        ];
+
+        FileMapDeserializer {
+            fields: fields.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for FileMapDeserializer {
+    type Error = DeserializeError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.fields.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for Value<SavedFile> {
+    type Error = DeserializeError;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Map(map) => visitor.visit_map(MapDeserializer::new(map)),
+            Value::Array(arr) => visitor.visit_seq(SeqDeserializer {
+                iter: arr.into_iter(),
+            }),
+            Value::File(file) => visitor.visit_map(FileMapDeserializer::new(file)),
+            Value::Text(s) => visitor.visit_string(s),
+            Value::Int(i) => visitor.visit_i64(i),
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::Bytes(b) => visitor.visit_byte_buf(b.to_vec()),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::File(file) => {
+                visitor.visit_string(file.stored_as.to_string_lossy().into_owned())
+            }
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char bytes byte_buf
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use types::SavedFile;
+
+    use super::*;
+
+    fn map(entries: Vec<(&str, Value<SavedFile>)>) -> Value<SavedFile> {
+        Value::Map(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v))
+                .collect(),
+        )
+    }
+
+    fn file() -> SavedFile {
+        SavedFile {
+            filename: "cat.png".to_owned(),
+            stored_as: PathBuf::from("/uploads/abc123"),
+            expires_at: None,
+            delete_on_download: false,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Leaf {
+        name: String,
+        count: i64,
+        ratio: f64,
+    }
+
+    #[test]
+    fn deserializes_a_simple_map() {
+        let value = map(vec![
+            ("name", Value::Text("hello".to_owned())),
+            ("count", Value::Int(3)),
+            ("ratio", Value::Float(0.5)),
+        ]);
+
+        let leaf: Leaf = value.deserialize_into().unwrap();
+
+        assert_eq!(
+            leaf,
+            Leaf {
+                name: "hello".to_owned(),
+                count: 3,
+                ratio: 0.5,
+            }
+        );
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Nested {
+        leaf: Leaf,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn deserializes_nested_structs_and_arrays() {
+        let value = map(vec![
+            (
+                "leaf",
+                map(vec![
+                    ("name", Value::Text("inner".to_owned())),
+                    ("count", Value::Int(1)),
+                    ("ratio", Value::Float(1.5)),
+                ]),
+            ),
+            (
+                "tags",
+                Value::Array(vec![
+                    Value::Text("a".to_owned()),
+                    Value::Text("b".to_owned()),
+                ]),
+            ),
+        ]);
+
+        let nested: Nested = value.deserialize_into().unwrap();
+
+        assert_eq!(
+            nested,
+            Nested {
+                leaf: Leaf {
+                    name: "inner".to_owned(),
+                    count: 1,
+                    ratio: 1.5,
+                },
+                tags: vec!["a".to_owned(), "b".to_owned()],
+            }
+        );
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct WithOption {
+        title: Option<String>,
+    }
+
+    #[test]
+    fn deserializes_present_and_absent_options() {
+        let present = map(vec![("title", Value::Text("hi".to_owned()))]);
+        let absent = map(vec![]);
+
+        let present: WithOption = present.deserialize_into().unwrap();
+        let absent: WithOption = absent.deserialize_into().unwrap();
+
+        assert_eq!(
+            present,
+            WithOption {
+                title: Some("hi".to_owned()),
+            }
+        );
+        assert_eq!(absent, WithOption { title: None });
+    }
+
+    #[test]
+    fn deserializes_a_file_as_its_stored_path() {
+        let value: String = Value::File(file()).deserialize_into().unwrap();
+
+        assert_eq!(value, "/uploads/abc123");
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct FileFields {
+        filename: String,
+        stored_as: String,
+    }
+
+    #[test]
+    fn deserializes_a_file_as_a_filename_stored_as_struct() {
+        let value: FileFields = Value::File(file()).deserialize_into().unwrap();
+
+        assert_eq!(
+            value,
+            FileFields {
+                filename: "cat.png".to_owned(),
+                stored_as: "/uploads/abc123".to_owned(),
+            }
+        );
+    }
+}
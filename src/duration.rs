@@ -0,0 +1,81 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::time::Duration;
+
+use error::Error;
+
+/// Parse a human-readable duration like `"24h"`, `"30m"`, or `"7d"` into a `Duration`.
+///
+/// Recognized suffixes are `s`/`sec`/`secs`, `m`/`min`/`mins`, `h`/`hr`/`hrs`, and
+/// `d`/`day`/`days`, matched case-insensitively. A bare number is treated as seconds.
+pub(crate) fn parse_duration(input: &str) -> Result<Duration, Error> {
+    let input = input.trim();
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or_else(|| input.len());
+
+    let (number, suffix) = input.split_at(split_at);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| Error::DurationFormat(input.to_owned()))?;
+
+    let seconds = match suffix.trim().to_lowercase().as_str() {
+        "" | "s" | "sec" | "secs" => number,
+        "m" | "min" | "mins" => number * 60.0,
+        "h" | "hr" | "hrs" => number * 3600.0,
+        "d" | "day" | "days" => number * 86400.0,
+        _ => return Err(Error::DurationFormat(input.to_owned())),
+    };
+
+    Ok(Duration::from_millis((seconds.max(0.0) * 1000.0) as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_bare_number_as_seconds() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn parses_each_recognized_suffix() {
+        assert_eq!(parse_duration("45s").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("5min").unwrap(), Duration::from_secs(5 * 60));
+        assert_eq!(parse_duration("2hrs").unwrap(), Duration::from_secs(2 * 3600));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(7 * 86400));
+    }
+
+    #[test]
+    fn matches_suffixes_case_insensitively() {
+        assert_eq!(parse_duration("24H").unwrap(), Duration::from_secs(24 * 3600));
+    }
+
+    #[test]
+    fn rejects_an_unrecognized_suffix() {
+        assert!(parse_duration("10 weeks").is_err());
+    }
+
+    #[test]
+    fn rejects_a_non_numeric_value() {
+        assert!(parse_duration("h").is_err());
+    }
+}
@@ -47,16 +47,22 @@ pub enum Error {
     Field,
     #[fail(display = "Too many fields in request")]
     FieldCount,
-    #[fail(display = "Field too large")]
-    FieldSize,
+    #[fail(display = "Field '{}' too large", _0)]
+    FieldSize(String),
     #[fail(display = "Found field with unexpected name or type")]
     FieldType,
     #[fail(display = "Failed to parse filename")]
     Filename,
     #[fail(display = "Too many files in request")]
     FileCount,
-    #[fail(display = "File too large")]
-    FileSize,
+    #[fail(display = "File '{}' too large", _0)]
+    FileSize(String),
+    #[fail(display = "Invalid size limit, {}", _0)]
+    SizeFormat(String),
+    #[fail(display = "Invalid duration, {}", _0)]
+    DurationFormat(String),
+    #[fail(display = "Invalid boolean, {}", _0)]
+    ParseBool(String),
 }
 
 impl From<MultipartError> for Error {
@@ -91,11 +97,14 @@ impl ResponseError for Error {
             | Error::ContentDisposition
             | Error::Field
             | Error::FieldCount
-            | Error::FieldSize
+            | Error::FieldSize(_)
             | Error::FieldType
             | Error::Filename
             | Error::FileCount
-            | Error::FileSize => HttpResponse::BadRequest().finish(),
+            | Error::FileSize(_)
+            | Error::SizeFormat(_)
+            | Error::DurationFormat(_)
+            | Error::ParseBool(_) => HttpResponse::BadRequest().finish(),
         }
     }
 }
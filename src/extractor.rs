@@ -0,0 +1,97 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use futures::{Future, future::result};
+
+use error::Error;
+use types::{Form, Value};
+use upload::handle_multipart;
+
+/// Implemented by a type that can be produced straight from a multipart request.
+///
+/// `form` describes the shape `handle_multipart` should parse the request into, and `extract`
+/// turns the resulting `Value` into `Self`. `Item` is whatever that `Form`'s `FileSink`s produce
+/// for `File` fields (e.g. `SavedFile`, the result of `disk_sink`); it has no default, so every
+/// implementation names it.
+///
+/// A `#[derive(FromMultipart)]` struct can implement this by delegating `extract` to
+/// `FromMultipart::from_multipart`.
+pub trait FormData: Sized {
+    /// What this form's `FileSink`s produce for `File` fields.
+    type Item;
+
+    /// The `Form` that `Multipart<Self>` parses the request body with.
+    fn form() -> Form<Self::Item>;
+
+    /// Convert the consolidated `Value` produced by `handle_multipart` into `Self`.
+    fn extract(value: Value<Self::Item>) -> Result<Self, Error>;
+}
+
+/// An extractor that runs `handle_multipart` against `D::form()` and converts the result with
+/// `D::extract`, so a handler can take `Multipart<D>` as an argument instead of parsing the
+/// request body by hand.
+///
+/// # Example
+/// ```rust
+/// # extern crate actix_web;
+/// # extern crate form_data;
+/// # use form_data::{Error, Field, Form, FormData, Value};
+/// struct Upload {
+///     title: String,
+/// }
+///
+/// impl FormData for Upload {
+///     type Item = form_data::SavedFile;
+///
+///     fn form() -> Form {
+///         Form::new().field("title", Field::text())
+///     }
+///
+///     fn extract(value: Value) -> Result<Self, Error> {
+///         match value {
+///             Value::Map(mut map) => match map.remove("title") {
+///                 Some(Value::Text(title)) => Ok(Upload { title }),
+///                 _ => Err(Error::FieldType),
+///             },
+///             _ => Err(Error::FieldType),
+///         }
+///     }
+/// }
+/// ```
+pub struct Multipart<D>(pub D);
+
+impl<D, S> FromRequest<S> for Multipart<D>
+where
+    D: FormData + 'static,
+    D::Item: Clone + 'static,
+    S: 'static,
+{
+    type Config = ();
+    type Result = Box<Future<Item = Self, Error = actix_web::Error>>;
+
+    fn from_request(req: &HttpRequest<S>, _cfg: &Self::Config) -> Self::Result {
+        Box::new(
+            handle_multipart(req.multipart(), D::form())
+                .and_then(|value| result(D::extract(value)))
+                .map(Multipart)
+                .map_err(actix_web::Error::from),
+        )
+    }
+}
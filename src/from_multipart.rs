@@ -0,0 +1,187 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+
+use types::{SavedFile, Value};
+
+/// The error produced when a consolidated `Value` doesn't match the shape of a
+/// `#[derive(FromMultipart)]` struct.
+#[derive(Debug, Fail)]
+pub enum FromMultipartError {
+    #[fail(display = "Expected a Map value")]
+    NotAMap,
+    #[fail(display = "Missing required field '{}'", _0)]
+    MissingField(String),
+    #[fail(display = "Field '{}' had an unexpected type, expected {}", field, expected)]
+    TypeMismatch {
+        field: String,
+        expected: &'static str,
+    },
+}
+
+/// Implemented by `#[derive(FromMultipart)]` for the target struct itself.
+///
+/// The derive macro generates the body of `from_multipart`, pulling each struct field out of
+/// the `Map` produced by `consolidate` via `FromMultipartField`.
+pub trait FromMultipart: Sized {
+    fn from_multipart(value: Value<SavedFile>) -> Result<Self, FromMultipartError>;
+}
+
+/// Implemented for the type of each field of a `#[derive(FromMultipart)]` struct.
+///
+/// Blanket-implemented for any `FromMultipartValue`, plus `Option<T>` (absent key becomes
+/// `None`) and `Vec<T>` (pulled from a `Value::Array`).
+pub trait FromMultipartField: Sized {
+    fn from_field(
+        map: &mut HashMap<String, Value<SavedFile>>,
+        key: &str,
+    ) -> Result<Self, FromMultipartError>;
+}
+
+/// Implemented for the types a single `Value` node can be converted into.
+pub trait FromMultipartValue: Sized {
+    fn from_value(value: Value<SavedFile>) -> Result<Self, FromMultipartError>;
+}
+
+impl FromMultipartValue for String {
+    fn from_value(value: Value<SavedFile>) -> Result<Self, FromMultipartError> {
+        match value {
+            Value::Text(s) => Ok(s),
+            _ => Err(FromMultipartError::TypeMismatch {
+                field: String::new(),
+                expected: "Text",
+            }),
+        }
+    }
+}
+
+impl FromMultipartValue for i64 {
+    fn from_value(value: Value<SavedFile>) -> Result<Self, FromMultipartError> {
+        match value {
+            Value::Int(i) => Ok(i),
+            _ => Err(FromMultipartError::TypeMismatch {
+                field: String::new(),
+                expected: "Int",
+            }),
+        }
+    }
+}
+
+impl FromMultipartValue for f64 {
+    fn from_value(value: Value<SavedFile>) -> Result<Self, FromMultipartError> {
+        match value {
+            Value::Float(f) => Ok(f),
+            _ => Err(FromMultipartError::TypeMismatch {
+                field: String::new(),
+                expected: "Float",
+            }),
+        }
+    }
+}
+
+impl FromMultipartValue for Bytes {
+    fn from_value(value: Value<SavedFile>) -> Result<Self, FromMultipartError> {
+        match value {
+            Value::Bytes(b) => Ok(b),
+            _ => Err(FromMultipartError::TypeMismatch {
+                field: String::new(),
+                expected: "Bytes",
+            }),
+        }
+    }
+}
+
+impl FromMultipartValue for SavedFile {
+    fn from_value(value: Value<SavedFile>) -> Result<Self, FromMultipartError> {
+        match value {
+            Value::File(file) => Ok(file),
+            _ => Err(FromMultipartError::TypeMismatch {
+                field: String::new(),
+                expected: "File",
+            }),
+        }
+    }
+}
+
+fn fill_field_name<T>(key: &str, res: Result<T, FromMultipartError>) -> Result<T, FromMultipartError> {
+    res.map_err(|e| match e {
+        FromMultipartError::TypeMismatch { expected, .. } => FromMultipartError::TypeMismatch {
+            field: key.to_owned(),
+            expected,
+        },
+        e => e,
+    })
+}
+
+impl<T> FromMultipartField for T
+where
+    T: FromMultipartValue,
+{
+    fn from_field(
+        map: &mut HashMap<String, Value<SavedFile>>,
+        key: &str,
+    ) -> Result<Self, FromMultipartError> {
+        match map.remove(key) {
+            Some(value) => fill_field_name(key, T::from_value(value)),
+            None => Err(FromMultipartError::MissingField(key.to_owned())),
+        }
+    }
+}
+
+impl<T> FromMultipartField for Option<T>
+where
+    T: FromMultipartValue,
+{
+    fn from_field(
+        map: &mut HashMap<String, Value<SavedFile>>,
+        key: &str,
+    ) -> Result<Self, FromMultipartError> {
+        match map.remove(key) {
+            Some(value) => fill_field_name(key, T::from_value(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T> FromMultipartField for Vec<T>
+where
+    T: FromMultipartValue,
+{
+    fn from_field(
+        map: &mut HashMap<String, Value<SavedFile>>,
+        key: &str,
+    ) -> Result<Self, FromMultipartError> {
+        match map.remove(key) {
+            Some(Value::Array(items)) => items
+                .into_iter()
+                .map(|item| fill_field_name(key, T::from_value(item)))
+                .collect(),
+            Some(_) => Err(FromMultipartError::TypeMismatch {
+                field: key.to_owned(),
+                expected: "Array",
+            }),
+            // `consolidate` never inserts a map entry for an array field with no submitted
+            // elements, so a missing key means "no items", not "missing field".
+            None => Ok(Vec::new()),
+        }
+    }
+}
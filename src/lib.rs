@@ -33,7 +33,7 @@
 //! use std::path::PathBuf;
 //!
 //! use actix_web::{http, server, App, AsyncResponder, HttpMessage, HttpRequest, HttpResponse, State};
-//! use form_data::{handle_multipart, Error, Field, FilenameGenerator, Form};
+//! use form_data::{disk_sink, handle_multipart, Error, Field, FilenameGenerator, Form};
 //! use futures::Future;
 //!
 //! struct Gen;
@@ -68,7 +68,7 @@
 //!                 .field("Two", Field::float())
 //!                 .finalize(),
 //!         )
-//!         .field("files", Field::array(Field::file(Gen)));
+//!         .field("files", Field::array(Field::file(disk_sink(Gen)).finalize()));
 //!
 //!     println!("{:?}", form);
 //!
@@ -92,17 +92,33 @@ extern crate http;
 extern crate log;
 extern crate mime;
 #[cfg(feature = "with-serde")]
+#[macro_use]
 extern crate serde;
 #[cfg(feature = "with-serde")]
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate tracing;
 
 use std::path::PathBuf;
 
+#[cfg(feature = "with-serde")]
+mod de;
+mod duration;
 mod error;
+mod extractor;
+mod from_multipart;
+mod sink;
+mod size;
 mod types;
 mod upload;
+#[cfg(feature = "with-serde")]
+pub use self::de::DeserializeError;
 pub use self::error::Error;
+pub use self::extractor::{FormData, Multipart};
+pub use self::from_multipart::{FromMultipart, FromMultipartError, FromMultipartField,
+                                FromMultipartValue};
+pub use self::sink::{disk_sink, disk_sink_with_backend, FsBackend, StorageBackend};
 pub use self::types::*;
 pub use self::upload::handle_multipart;
 
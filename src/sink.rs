@@ -0,0 +1,179 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::{fs::DirBuilder, io, path::{Path, PathBuf}, sync::Arc};
+#[cfg(unix)]
+use std::os::unix::fs::DirBuilderExt;
+
+use bytes::Bytes;
+use futures::{Future, Sink, Stream, future::result};
+use futures_cpupool::CpuPool;
+use futures_fs::FsPool;
+use tracing::Span;
+
+use error::Error;
+use types::{FileSink, FileStream, NamePart, SavedFile};
+use super::FilenameGenerator;
+
+/// A place a `FileSink` can write the bytes of an upload to, keyed by path.
+///
+/// `FsBackend` is the provided implementation that writes to the local filesystem; implement
+/// this trait to target object storage, an in-memory store for tests, or anywhere else.
+pub trait StorageBackend: Send + Sync {
+    /// Open a sink that accepts the bytes of the file stored at `key`.
+    fn open_sink(
+        &self,
+        key: &Path,
+        content_type: &mime::Mime,
+    ) -> Box<Future<Item = Box<Sink<SinkItem = Bytes, SinkError = io::Error> + Send>, Error = Error>
+             + Send>;
+}
+
+/// The provided `StorageBackend` that writes uploads onto the local filesystem.
+///
+/// Runs its own `CpuPool` to perform the (blocking) directory creation and writes, so it
+/// doesn't depend on the `Form` that ends up using it. Directory permissions are only set to
+/// `0o755` on unix; other platforms get the target directory's default permissions.
+#[derive(Clone)]
+pub struct FsBackend {
+    pool: CpuPool,
+}
+
+impl FsBackend {
+    pub fn new() -> Self {
+        FsBackend {
+            pool: CpuPool::new_num_cpus(),
+        }
+    }
+}
+
+impl Default for FsBackend {
+    fn default() -> Self {
+        FsBackend::new()
+    }
+}
+
+fn create_dir(dir: PathBuf) -> Result<(), Error> {
+    let mut builder = DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    builder.mode(0o755);
+
+    builder.create(dir).map_err(|_| Error::MkDir)
+}
+
+impl StorageBackend for FsBackend {
+    fn open_sink(
+        &self,
+        key: &Path,
+        _content_type: &mime::Mime,
+    ) -> Box<Future<Item = Box<Sink<SinkItem = Bytes, SinkError = io::Error> + Send>, Error = Error>
+             + Send> {
+        let mut dir = key.to_path_buf();
+        dir.pop();
+
+        let fs_pool = FsPool::from_executor(self.pool.clone());
+        let key = key.to_path_buf();
+
+        Box::new(self.pool.spawn_fn(move || create_dir(dir)).map(move |_| {
+            Box::new(fs_pool.write(key, Default::default()))
+                as Box<Sink<SinkItem = Bytes, SinkError = io::Error> + Send>
+        }))
+    }
+}
+
+/// Stream an upload onto whichever `StorageBackend` the owning `Form` currently holds (its
+/// `backend` field, `FsBackend` by default), using a `FilenameGenerator` to pick the destination
+/// path.
+pub fn disk_sink<G>(gen: G) -> impl FileSink<SavedFile>
+where
+    G: FilenameGenerator + 'static,
+{
+    DiskSink { gen, backend: None }
+}
+
+/// Like `disk_sink`, but always writing through `backend`, ignoring whatever the owning `Form`
+/// is currently set to - for a field that needs to target a specific backend regardless of the
+/// rest of the form.
+pub fn disk_sink_with_backend<G, B>(gen: G, backend: B) -> impl FileSink<SavedFile>
+where
+    G: FilenameGenerator + 'static,
+    B: StorageBackend + 'static,
+{
+    DiskSink {
+        gen,
+        backend: Some(Arc::new(backend) as Arc<StorageBackend>),
+    }
+}
+
+struct DiskSink<G> {
+    gen: G,
+    backend: Option<Arc<StorageBackend>>,
+}
+
+impl<G> FileSink<SavedFile> for DiskSink<G>
+where
+    G: FilenameGenerator + 'static,
+{
+    fn call(
+        &self,
+        _name: Vec<NamePart>,
+        filename: Option<String>,
+        content_type: mime::Mime,
+        stream: FileStream,
+        backend: Arc<StorageBackend>,
+        span: Span,
+    ) -> Box<Future<Item = SavedFile, Error = Error>> {
+        let _enter = span.enter();
+
+        let filename = match filename {
+            Some(filename) => filename,
+            None => return Box::new(result(Err(Error::Filename))),
+        };
+
+        let path: &Path = filename.as_ref();
+        let filename = match path.file_name().and_then(|filename| filename.to_str()) {
+            Some(filename) => filename.to_owned(),
+            None => return Box::new(result(Err(Error::Filename))),
+        };
+
+        let stored_as = match self.gen.next_filename(&content_type) {
+            Some(file_path) => file_path,
+            None => return Box::new(result(Err(Error::GenFilename))),
+        };
+
+        debug!("Storing upload as {:?}", stored_as);
+
+        let backend = self.backend.clone().unwrap_or(backend);
+
+        Box::new(
+            backend
+                .open_sink(&stored_as, &content_type)
+                .and_then(move |write| {
+                    stream.forward(write).map(move |_| SavedFile {
+                        filename,
+                        stored_as,
+                        expires_at: None,
+                        delete_on_download: false,
+                    })
+                }),
+        )
+    }
+}
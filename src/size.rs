@@ -0,0 +1,83 @@
+/*
+ * This file is part of Actix Form Data.
+ *
+ * Copyright © 2018 Riley Trautman
+ *
+ * Actix Form Data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Actix Form Data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use error::Error;
+
+/// Parse a human-readable size like `"32 MiB"`, `"16KB"`, or `"128"` into a byte count.
+///
+/// Binary suffixes (`KiB`, `MiB`, `GiB`) multiply by powers of 1024; decimal suffixes (`KB`,
+/// `MB`, `GB`) multiply by powers of 1000. A bare number is treated as a byte count. Suffixes
+/// are matched case-insensitively.
+pub(crate) fn parse_size(input: &str) -> Result<u64, Error> {
+    let input = input.trim();
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or_else(|| input.len());
+
+    let (number, suffix) = input.split_at(split_at);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| Error::SizeFormat(input.to_owned()))?;
+
+    let multiplier = match suffix.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000 * 1000,
+        "gb" => 1000 * 1000 * 1000,
+        "kib" => 1024,
+        "mib" => 1024 * 1024,
+        "gib" => 1024 * 1024 * 1024,
+        _ => return Err(Error::SizeFormat(input.to_owned())),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_bare_number_as_bytes() {
+        assert_eq!(parse_size("128").unwrap(), 128);
+    }
+
+    #[test]
+    fn parses_decimal_suffixes() {
+        assert_eq!(parse_size("16KB").unwrap(), 16_000);
+        assert_eq!(parse_size("1MB").unwrap(), 1_000_000);
+        assert_eq!(parse_size("1GB").unwrap(), 1_000_000_000);
+    }
+
+    #[test]
+    fn parses_binary_suffixes_case_insensitively() {
+        assert_eq!(parse_size("32 MiB").unwrap(), 32 * 1024 * 1024);
+        assert_eq!(parse_size("1gib").unwrap(), 1024 * 1024 * 1024);
+    }
+
+    #[test]
+    fn rejects_an_unrecognized_suffix() {
+        assert!(parse_size("10 furlongs").is_err());
+    }
+
+    #[test]
+    fn rejects_a_non_numeric_value() {
+        assert!(parse_size("KB").is_err());
+    }
+}
@@ -17,17 +17,111 @@
  * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::{fmt, collections::{HashMap, VecDeque}, path::PathBuf, sync::Arc};
+use std::{fmt, collections::{HashMap, VecDeque}, path::PathBuf, sync::Arc, time::{Duration, SystemTime}};
 
 use bytes::Bytes;
-use futures::{Future, future::{ExecuteError, Executor}};
+use futures::{Future, Stream, future::{ExecuteError, Executor}};
 use futures_cpupool::CpuPool;
+use tracing::Span;
 
+use duration::parse_duration;
+use error::Error;
+use sink::{FsBackend, StorageBackend};
+use size::parse_size;
 use super::FilenameGenerator;
 
+/// The stream of bytes handed to a `FileSink` for a single field.
+///
+/// Chunks are already counted against the form's size limits by the time the sink sees them, so
+/// a sink only needs to worry about what to do with the bytes, not how many of them there are.
+///
+/// Not `Send`: it's built directly from actix-web's `multipart::Field`, which wraps the
+/// connection's `Rc<RefCell<_>>`-based payload and is never `Send` itself.
+pub type FileStream = Box<Stream<Item = Bytes, Error = Error>>;
+
+/// A trait for types that consume a streamed file upload and produce a result.
+///
+/// Unlike `FilenameGenerator`, a `FileSink` is handed the field's byte stream directly, so it can
+/// push the bytes anywhere (disk, object storage, memory, a hasher) instead of being limited to
+/// producing a path that this crate writes to. Use `disk_sink` to recover the old
+/// write-to-filesystem behavior. `T` is whatever the sink produces once it's done with the
+/// stream, and becomes the payload of `Value::File`/`MultipartContent::File` for that field.
+///
+/// `backend` is the owning `Form`'s current `StorageBackend` (swappable at runtime via
+/// `Form::backend`); `disk_sink` writes through it instead of baking one in at construction
+/// time. A sink that doesn't write to disk can ignore it.
+///
+/// `span` is this file's span in `handle_multipart`'s per-request span tree (a child of the
+/// field's span, itself a child of the form's). It's entered for the duration of the upload, so
+/// a sink can attach its own events to it via `tracing`'s macros without having to build its own
+/// context from `name`.
+pub trait FileSink<T>: Send + Sync {
+    fn call(
+        &self,
+        name: Vec<NamePart>,
+        filename: Option<String>,
+        content_type: mime::Mime,
+        stream: FileStream,
+        backend: Arc<StorageBackend>,
+        span: Span,
+    ) -> Box<Future<Item = T, Error = Error>>;
+}
+
+impl<F, T, Fut> FileSink<T> for F
+where
+    F: Fn(Vec<NamePart>, Option<String>, mime::Mime, FileStream, Arc<StorageBackend>, Span) -> Fut
+        + Send
+        + Sync,
+    Fut: Future<Item = T, Error = Error> + 'static,
+{
+    fn call(
+        &self,
+        name: Vec<NamePart>,
+        filename: Option<String>,
+        content_type: mime::Mime,
+        stream: FileStream,
+        backend: Arc<StorageBackend>,
+        span: Span,
+    ) -> Box<Future<Item = T, Error = Error>> {
+        Box::new((self)(name, filename, content_type, stream, backend, span))
+    }
+}
+
+/// Implemented by a `FileSink`'s output type so `handle_multipart` can attach retention
+/// metadata parsed from a `Form`'s `FileControl` fields onto it.
+///
+/// `disk_sink`'s `SavedFile` implements this. A sink whose output has no notion of expiry can
+/// implement this as a no-op.
+pub trait FileMetadata: Sized {
+    fn with_control(self, expires_at: Option<SystemTime>, delete_on_download: bool) -> Self;
+}
+
+/// The result of a `disk_sink` (or `disk_sink_with_backend`) upload.
+///
+/// `expires_at`/`delete_on_download` start unset and are filled in by `handle_multipart` from
+/// the owning `Form`'s `FileControl` fields, if any were declared.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedFile {
+    pub filename: String,
+    pub stored_as: PathBuf,
+    pub expires_at: Option<SystemTime>,
+    pub delete_on_download: bool,
+}
+
+impl FileMetadata for SavedFile {
+    fn with_control(mut self, expires_at: Option<SystemTime>, delete_on_download: bool) -> Self {
+        self.expires_at = expires_at;
+        self.delete_on_download = delete_on_download;
+
+        self
+    }
+}
+
 /// The result of a succesfull parse through a given multipart stream.
 ///
-/// This type represents all possible variations in structure of a Multipart Form.
+/// This type represents all possible variations in structure of a Multipart Form. `T` is the
+/// type a `FileSink` produces for this form's `File` fields; it defaults to `SavedFile`, the
+/// result of the provided `disk_sink`.
 ///
 /// # Example usage
 ///
@@ -51,18 +145,21 @@ use super::FilenameGenerator;
 /// }
 /// ```
 #[derive(Clone, Debug, PartialEq)]
-pub enum Value {
-    Map(HashMap<String, Value>),
-    Array(Vec<Value>),
-    File(String, PathBuf),
+pub enum Value<T = SavedFile> {
+    Map(HashMap<String, Value<T>>),
+    Array(Vec<Value<T>>),
+    File(T),
     Text(String),
     Int(i64),
     Float(f64),
     Bytes(Bytes),
 }
 
-impl Value {
-    pub(crate) fn merge(&mut self, rhs: Self) {
+impl<T> Value<T> {
+    pub(crate) fn merge(&mut self, rhs: Self)
+    where
+        T: Clone,
+    {
         match (self, rhs) {
             (&mut Value::Map(ref mut hm), Value::Map(ref other)) => {
                 other.into_iter().fold(hm, |hm, (key, value)| {
@@ -83,13 +180,10 @@ impl Value {
     }
 }
 
-impl From<MultipartContent> for Value {
-    fn from(mc: MultipartContent) -> Self {
+impl<T> From<MultipartContent<T>> for Value<T> {
+    fn from(mc: MultipartContent<T>) -> Self {
         match mc {
-            MultipartContent::File {
-                filename,
-                stored_as,
-            } => Value::File(filename, stored_as),
+            MultipartContent::File(file) => Value::File(file),
             MultipartContent::Text(string) => Value::Text(string),
             MultipartContent::Int(i) => Value::Int(i),
             MultipartContent::Float(f) => Value::Float(f),
@@ -98,65 +192,193 @@ impl From<MultipartContent> for Value {
     }
 }
 
+/// Check `detected` against an optional MIME allowlist, where a wildcard top-level or subtype
+/// (e.g. `image/*`) matches anything. `None` accepts everything.
+fn mime_allowed(allowed_types: &Option<Arc<[mime::Mime]>>, detected: &mime::Mime) -> bool {
+    match *allowed_types {
+        Some(ref types) => types.iter().any(|allowed| {
+            (allowed.type_() == mime::STAR || allowed.type_() == detected.type_())
+                && (allowed.subtype() == mime::STAR || allowed.subtype() == detected.subtype())
+        }),
+        None => true,
+    }
+}
+
+/// A `File` field's sink, together with the MIME types it will accept.
+///
+/// Built via `Field::file` and refined with `allowed_types`.
+pub struct FileSpec<T = SavedFile> {
+    pub(crate) sink: Arc<FileSink<T>>,
+    pub(crate) allowed_types: Option<Arc<[mime::Mime]>>,
+    pub(crate) max_size: Option<u64>,
+}
+
+// Hand-written so cloning a `FileSpec<T>` never requires `T: Clone` - every field clones through
+// an `Arc`/`Option<u64>` without ever touching a `T` value, unlike what `#[derive(Clone)]` would
+// assume.
+impl<T> Clone for FileSpec<T> {
+    fn clone(&self) -> Self {
+        FileSpec {
+            sink: self.sink.clone(),
+            allowed_types: self.allowed_types.clone(),
+            max_size: self.max_size,
+        }
+    }
+}
+
+impl<T> FileSpec<T> {
+    /// Restrict this field to files whose sniffed content type matches one of `types`.
+    ///
+    /// A wildcard top-level type (e.g. `image/*`) matches any subtype. Uploads whose detected
+    /// type isn't in the list are rejected with `Error::ContentType` before the sink is called.
+    pub fn allowed_types(mut self, types: &[mime::Mime]) -> Self {
+        self.allowed_types = Some(types.to_vec().into());
+
+        self
+    }
+
+    /// Cap this field's size. `Form::max_file_size` still applies as well; the smaller of the
+    /// two limits wins.
+    ///
+    /// Accepts human-readable sizes like `"32 MiB"` or `"1.5 GB"`. Panics if `size` can't be
+    /// parsed, since this is meant to be called with a literal at form-construction time.
+    pub fn max_size(mut self, size: &str) -> Self {
+        self.max_size = Some(parse_size(size).expect("Invalid size limit"));
+
+        self
+    }
+
+    /// Finalize this file field, so it can be added to a Form or Map.
+    pub fn finalize(self) -> Field<T> {
+        Field::File(self)
+    }
+
+    pub(crate) fn accepts(&self, detected: &mime::Mime) -> bool {
+        mime_allowed(&self.allowed_types, detected)
+    }
+}
+
+/// A `Bytes` field's optional size cap and MIME allowlist.
+///
+/// Built via `Field::bytes` and refined with `allowed_types`/`max_size`.
+#[derive(Clone, Debug, Default)]
+pub struct BytesSpec {
+    pub(crate) allowed_types: Option<Arc<[mime::Mime]>>,
+    pub(crate) max_size: Option<u64>,
+}
+
+impl BytesSpec {
+    /// Restrict this field to parts whose declared `Content-Type` matches one of `types`.
+    ///
+    /// A wildcard top-level type (e.g. `text/*`) matches any subtype. Unlike a `File` field,
+    /// this is checked against the submitted part's `Content-Type` header directly (there's no
+    /// stream to sniff a handful of arbitrary bytes from), before any of the field is read.
+    /// Mismatches are rejected with `Error::ContentType`.
+    pub fn allowed_types(mut self, types: &[mime::Mime]) -> Self {
+        self.allowed_types = Some(types.to_vec().into());
+
+        self
+    }
+
+    /// Cap this field's size. `Form::max_field_size` still applies as well; the smaller of the
+    /// two limits wins.
+    ///
+    /// Accepts human-readable sizes like `"16 KiB"` or `"2 MB"`. Panics if `size` can't be
+    /// parsed, since this is meant to be called with a literal at form-construction time.
+    pub fn max_size(mut self, size: &str) -> Self {
+        self.max_size = Some(parse_size(size).expect("Invalid size limit"));
+
+        self
+    }
+
+    /// Finalize this bytes field, so it can be added to a Form or Map.
+    pub fn finalize<T>(self) -> Field<T> {
+        Field::Bytes(self)
+    }
+
+    pub(crate) fn accepts(&self, detected: &mime::Mime) -> bool {
+        mime_allowed(&self.allowed_types, detected)
+    }
+}
+
 /// The field type represents a field in the form-data that is allowed to be parsed.
-#[derive(Clone)]
-pub enum Field {
-    Array(Array),
-    File(Arc<FilenameGenerator>),
-    Map(Map),
-    Int,
-    Float,
-    Text,
-    Bytes,
+pub enum Field<T = SavedFile> {
+    Array(Array<T>),
+    File(FileSpec<T>),
+    Map(Map<T>),
+    Int(Option<u64>),
+    Float(Option<u64>),
+    Text(Option<u64>),
+    Bytes(BytesSpec),
 }
 
-impl fmt::Debug for Field {
+// Hand-written for the same reason as `FileSpec`'s `Clone` impl: none of these variants need
+// `T: Clone` to clone themselves, so a blanket derive would demand a bound nothing requires.
+impl<T> Clone for Field<T> {
+    fn clone(&self) -> Self {
+        match *self {
+            Field::Array(ref arr) => Field::Array(arr.clone()),
+            Field::File(ref spec) => Field::File(spec.clone()),
+            Field::Map(ref map) => Field::Map(map.clone()),
+            Field::Int(max_size) => Field::Int(max_size),
+            Field::Float(max_size) => Field::Float(max_size),
+            Field::Text(max_size) => Field::Text(max_size),
+            Field::Bytes(ref spec) => Field::Bytes(spec.clone()),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Field<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Field::Array(ref arr) => write!(f, "Array({:?})", arr),
-            Field::File(_) => write!(f, "File(filename_generator)"),
+            Field::File(_) => write!(f, "File(sink)"),
             Field::Map(ref map) => write!(f, "Map({:?})", map),
-            Field::Int => write!(f, "Int"),
-            Field::Float => write!(f, "Float"),
-            Field::Text => write!(f, "Text"),
-            Field::Bytes => write!(f, "Bytes"),
+            Field::Int(_) => write!(f, "Int"),
+            Field::Float(_) => write!(f, "Float"),
+            Field::Text(_) => write!(f, "Text"),
+            Field::Bytes(_) => write!(f, "Bytes"),
         }
     }
 }
 
-impl Field {
-    /// Add a File field with a name generator.
+impl<T> Field<T> {
+    /// Add a File field with a sink.
     ///
-    /// The name generator will be called for each file matching this field's key. Keep in mind
-    /// that each key/file pair will have it's own name-generator, so sharing a name-generator
-    /// between fields is up to the user.
+    /// The sink is called once per file matching this field's key, and receives the field's
+    /// name path, original filename, content type, its (size-capped) byte stream, the owning
+    /// `Form`'s `StorageBackend`, and this file's `tracing` span. It decides what to do with the bytes and returns the value to
+    /// store for this field (`T`), so uploads can be streamed to disk, object storage, memory,
+    /// or anywhere else.
     ///
     /// # Example
     /// ```rust
-    /// # extern crate mime;
+    /// # extern crate bytes;
     /// # extern crate form_data;
-    /// # use std::path::{Path, PathBuf};
-    /// # use form_data::{Form, Field, FilenameGenerator};
-    ///
-    /// struct Gen;
-    ///
-    /// impl FilenameGenerator for Gen {
-    ///     fn next_filename(&self, _: &mime::Mime) -> Option<PathBuf> {
-    ///         Some(AsRef::<Path>::as_ref("path.png").to_owned())
-    ///     }
-    /// }
-    ///
-    /// fn main() {
-    ///     let name_generator = Gen;
-    ///     let form = Form::new()
-    ///         .field("file-field", Field::file(name_generator));
-    /// }
+    /// # extern crate futures;
+    /// # extern crate mime;
+    /// # use form_data::{Form, Field};
+    /// # use futures::future::result;
+    /// # use bytes::Bytes;
+    /// # fn main() {
+    /// let form: Form<Bytes> = Form::new().field(
+    ///     "file-field",
+    ///     Field::file(|_name, _filename, _content_type, _stream, _backend, _span| {
+    ///         result(Ok(Bytes::new()))
+    ///     }).allowed_types(&[mime::IMAGE_PNG, mime::IMAGE_JPEG])
+    ///         .finalize(),
+    /// );
+    /// # }
     /// ```
-    pub fn file<T>(gen: T) -> Self
+    pub fn file<S>(sink: S) -> FileSpec<T>
     where
-        T: FilenameGenerator + 'static,
+        S: FileSink<T> + 'static,
     {
-        Field::File(Arc::new(gen))
+        FileSpec {
+            sink: Arc::new(sink),
+            allowed_types: None,
+            max_size: None,
+        }
     }
 
     /// Add a Text field to a form
@@ -166,10 +388,10 @@ impl Field {
     /// # extern crate form_data;
     /// # use form_data::{Form, Field};
     /// # fn main() {
-    /// let form = Form::new().field("text-field", Field::text());
+    /// let form: Form = Form::new().field("text-field", Field::text());
     /// # }
     pub fn text() -> Self {
-        Field::Text
+        Field::Text(None)
     }
 
     /// Add an Int field to a form
@@ -179,11 +401,11 @@ impl Field {
     /// # extern crate form_data;
     /// # use form_data::{Form, Field};
     /// # fn main() {
-    /// let form = Form::new().field("int-field", Field::int());
+    /// let form: Form = Form::new().field("int-field", Field::int());
     /// # }
     /// ```
     pub fn int() -> Self {
-        Field::Int
+        Field::Int(None)
     }
 
     /// Add a Float field to a form
@@ -193,25 +415,49 @@ impl Field {
     /// # extern crate form_data;
     /// # use form_data::{Form, Field};
     /// # fn main() {
-    /// let form = Form::new().field("float-field", Field::float());
+    /// let form: Form = Form::new().field("float-field", Field::float());
     /// # }
     /// ```
     pub fn float() -> Self {
-        Field::Float
+        Field::Float(None)
     }
 
-    /// Add a Bytes field to a form
+    /// Add a Bytes field with an optional size cap and MIME allowlist.
     ///
     /// # Example
     /// ```rust
     /// # extern crate form_data;
+    /// # extern crate mime;
     /// # use form_data::{Form, Field};
     /// # fn main() {
-    /// let form = Form::new().field("bytes-field", Field::bytes());
+    /// let form: Form = Form::new().field(
+    ///     "bytes-field",
+    ///     Field::bytes()
+    ///         .allowed_types(&[mime::TEXT_PLAIN])
+    ///         .finalize(),
+    /// );
     /// # }
     /// ```
-    pub fn bytes() -> Self {
-        Field::Bytes
+    pub fn bytes() -> BytesSpec {
+        BytesSpec::default()
+    }
+
+    /// Cap this field's size. `Form::max_field_size` still applies as well; the smaller of the
+    /// two limits wins.
+    ///
+    /// Accepts human-readable sizes like `"16 KiB"` or `"2 MB"`. Has no effect on `Array`,
+    /// `Bytes`, `Map`, or `File` fields - set a `Bytes`/`File` field's size cap via its own
+    /// `max_size` before calling `finalize`. Panics if `size` can't be parsed, since this is
+    /// meant to be called with a literal at form-construction time.
+    pub fn max_size(self, size: &str) -> Self {
+        let size = Some(parse_size(size).expect("Invalid size limit"));
+
+        match self {
+            Field::Int(_) => Field::Int(size),
+            Field::Float(_) => Field::Float(size),
+            Field::Text(_) => Field::Text(size),
+            other => other,
+        }
     }
 
     /// Add an Array to a form
@@ -221,14 +467,14 @@ impl Field {
     /// # extern crate form_data;
     /// # use form_data::{Form, Field};
     /// # fn main() {
-    /// let form = Form::new()
+    /// let form: Form = Form::new()
     ///     .field(
     ///         "array-field",
     ///         Field::array(Field::text())
     ///     );
     /// # }
     /// ```
-    pub fn array(field: Field) -> Self {
+    pub fn array(field: Field<T>) -> Self {
         Field::Array(Array::new(field))
     }
 
@@ -239,7 +485,7 @@ impl Field {
     /// # extern crate form_data;
     /// # use form_data::{Form, Field};
     /// # fn main() {
-    /// let form = Form::new()
+    /// let form: Form = Form::new()
     ///     .field(
     ///         "map-field",
     ///         Field::map()
@@ -249,61 +495,86 @@ impl Field {
     ///     );
     /// # }
     /// ```
-    pub fn map() -> Map {
+    pub fn map() -> Map<T> {
         Map::new()
     }
 
-    fn valid_field(&self, name: VecDeque<NamePart>) -> Option<FieldTerminator> {
+    fn valid_field(&self, name: VecDeque<NamePart>) -> Option<FieldTerminator<T>> {
         trace!("Checking {:?} and {:?}", self, name);
         match *self {
             Field::Array(ref arr) => arr.valid_field(name),
             Field::Map(ref map) => map.valid_field(name),
-            Field::File(ref gen) => if name.is_empty() {
-                Some(FieldTerminator::File(Arc::clone(gen)))
+            Field::File(ref spec) => if name.is_empty() {
+                Some(FieldTerminator::File(spec.clone()))
             } else {
                 None
             },
-            Field::Int => if name.is_empty() {
-                Some(FieldTerminator::Int)
+            Field::Int(max_size) => if name.is_empty() {
+                Some(FieldTerminator::Int(max_size))
             } else {
                 None
             },
-            Field::Float => if name.is_empty() {
-                Some(FieldTerminator::Float)
+            Field::Float(max_size) => if name.is_empty() {
+                Some(FieldTerminator::Float(max_size))
             } else {
                 None
             },
-            Field::Text => if name.is_empty() {
-                Some(FieldTerminator::Text)
+            Field::Text(max_size) => if name.is_empty() {
+                Some(FieldTerminator::Text(max_size))
             } else {
                 None
             },
-            Field::Bytes => if name.is_empty() {
-                Some(FieldTerminator::Bytes)
+            Field::Bytes(ref spec) => if name.is_empty() {
+                Some(FieldTerminator::Bytes(spec.clone()))
             } else {
                 None
             },
         }
     }
+
+    fn validate(&self) -> Result<(), FormError> {
+        match *self {
+            Field::Map(ref map) => map.validate(),
+            Field::Array(ref arr) => arr.validate(),
+            Field::File(_) | Field::Int(_) | Field::Float(_) | Field::Text(_) | Field::Bytes(_) => {
+                Ok(())
+            }
+        }
+    }
 }
 
 /// A definition of an array of type `Field` to be parsed from form data.
 ///
 /// The `Array` type should only be constructed in the context of a Form. See the `Form`
 /// documentation for more information.
-#[derive(Debug, Clone)]
-pub struct Array {
-    inner: Box<Field>,
+pub struct Array<T = SavedFile> {
+    inner: Box<Field<T>>,
+}
+
+// Hand-written: `Field<T>`'s `Clone`/`Debug` impls are already unconditional, so `Array<T>`'s
+// should be too, instead of the `T: Clone`/`T: Debug` bounds `#[derive]` would add.
+impl<T> Clone for Array<T> {
+    fn clone(&self) -> Self {
+        Array {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Array<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Array({:?})", self.inner)
+    }
 }
 
-impl Array {
-    fn new(field: Field) -> Self {
+impl<T> Array<T> {
+    fn new(field: Field<T>) -> Self {
         Array {
             inner: Box::new(field),
         }
     }
 
-    fn valid_field(&self, mut name: VecDeque<NamePart>) -> Option<FieldTerminator> {
+    fn valid_field(&self, mut name: VecDeque<NamePart>) -> Option<FieldTerminator<T>> {
         trace!("Checking {:?} and {:?}", self, name);
         match name.pop_front() {
             Some(name_part) => match name_part {
@@ -313,15 +584,34 @@ impl Array {
             None => None,
         }
     }
+
+    fn validate(&self) -> Result<(), FormError> {
+        self.inner.validate()
+    }
 }
 
 /// A definition of key-value pairs to be parsed from form data.
-#[derive(Debug, Clone)]
-pub struct Map {
-    inner: Vec<(String, Field)>,
+pub struct Map<T = SavedFile> {
+    inner: Vec<(String, Field<T>)>,
 }
 
-impl Map {
+// Hand-written for the same reason as `Array`'s: `Field<T>` clones/formats unconditionally, so
+// `Map<T>` should too, rather than picking up a `T: Clone`/`T: Debug` bound via `#[derive]`.
+impl<T> Clone for Map<T> {
+    fn clone(&self) -> Self {
+        Map {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Map<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Map({:?})", self.inner)
+    }
+}
+
+impl<T> Map<T> {
     fn new() -> Self {
         Map { inner: Vec::new() }
     }
@@ -331,31 +621,51 @@ impl Map {
     /// ```rust
     /// # use form_data::Field;
     /// #
-    /// Field::map()
+    /// let _: Field = Field::map()
     ///     .field("sub-field", Field::text())
     ///     .field("sub-field-two", Field::text())
     ///     .finalize();
     /// ```
-    pub fn field(mut self, key: &str, value: Field) -> Self {
+    pub fn field(mut self, key: &str, value: Field<T>) -> Self {
         self.inner.push((key.to_owned(), value));
 
         self
     }
 
+    /// Add a `Field` to a map, rejecting a `key` that's already present.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use form_data::{Field, FormError, Map};
+    /// #
+    /// let map: Result<Map, FormError> = Field::map()
+    ///     .try_field("sub-field", Field::text())
+    ///     .and_then(|map| map.try_field("sub-field", Field::int()));
+    ///
+    /// assert!(map.is_err());
+    /// ```
+    pub fn try_field(self, key: &str, value: Field<T>) -> Result<Self, FormError> {
+        if self.inner.iter().any(|&(ref item, _)| item.as_str() == key) {
+            return Err(FormError::DuplicateField(key.to_owned()));
+        }
+
+        Ok(self.field(key, value))
+    }
+
     /// Finalize the map into a `Field`, so it can be added to a Form
     /// ```rust
     /// # use form_data::Field;
     /// #
-    /// Field::map()
+    /// let _: Field = Field::map()
     ///     .field("sub-field", Field::text())
     ///     .field("sub-field-two", Field::text())
     ///     .finalize();
     /// ```
-    pub fn finalize(self) -> Field {
+    pub fn finalize(self) -> Field<T> {
         Field::Map(self)
     }
 
-    fn valid_field(&self, mut name: VecDeque<NamePart>) -> Option<FieldTerminator> {
+    fn valid_field(&self, mut name: VecDeque<NamePart>) -> Option<FieldTerminator<T>> {
         trace!("Checking {:?} and {:?}", self, name);
         match name.pop_front() {
             Some(name_part) => match name_part {
@@ -368,6 +678,50 @@ impl Map {
             None => None,
         }
     }
+
+    fn validate(&self) -> Result<(), FormError> {
+        if self.inner.is_empty() {
+            return Err(FormError::EmptyMap);
+        }
+
+        let mut seen = Vec::with_capacity(self.inner.len());
+
+        for &(ref key, ref field) in &self.inner {
+            if seen.contains(key) {
+                return Err(FormError::DuplicateField(key.clone()));
+            }
+
+            seen.push(key.clone());
+            field.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An error produced while validating a `Form`'s field tree.
+///
+/// Returned by `Form::try_field`/`Map::try_field` (a duplicate key, caught immediately at
+/// insertion) and `Form::finalize` (a full walk of the tree, catching duplicates introduced
+/// through the non-fallible `field` as well as structurally empty maps), so misconfiguration is
+/// caught at startup instead of producing silent parse failures at request time.
+#[derive(Debug, Fail)]
+pub enum FormError {
+    #[fail(display = "Duplicate field '{}'", _0)]
+    DuplicateField(String),
+    #[fail(display = "Map field has no entries, so it can never be satisfied")]
+    EmptyMap,
+}
+
+/// Names of the control fields a `Form` recognizes to configure a stored file's retention.
+///
+/// Set via `Form::file_control`. A submitted field matching `keep_for` or `delete_on_download`
+/// is consumed to set the resulting file's `expires_at`/`delete_on_download` (via
+/// `FileMetadata::with_control`) instead of appearing in the consolidated `Value` tree.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileControl {
+    pub keep_for: String,
+    pub delete_on_download: String,
 }
 
 /// A structure that defines the fields expected in form data
@@ -377,7 +731,7 @@ impl Map {
 /// # extern crate mime;
 /// # extern crate form_data;
 /// # use std::path::{Path, PathBuf};
-/// # use form_data::{Form, Field, FilenameGenerator};
+/// # use form_data::{disk_sink, Form, Field, FilenameGenerator};
 /// # struct Gen;
 /// # impl FilenameGenerator for Gen {
 /// #     fn next_filename(&self, _: &mime::Mime) -> Option<PathBuf> {
@@ -390,8 +744,8 @@ impl Map {
 ///     .field("field-name", Field::text())
 ///     .field("second-field", Field::int())
 ///     .field("third-field", Field::float())
-///     .field("fourth-field", Field::bytes())
-///     .field("fifth-field", Field::file(name_generator))
+///     .field("fourth-field", Field::bytes().finalize())
+///     .field("fifth-field", Field::file(disk_sink(name_generator)).finalize())
 ///     .field(
 ///         "map-field",
 ///         Field::map()
@@ -405,17 +759,47 @@ impl Map {
 ///     );
 /// # }
 /// ```
-#[derive(Clone)]
-pub struct Form {
+pub struct Form<T = SavedFile> {
     pub max_fields: u32,
     pub max_field_size: usize,
     pub max_files: u32,
     pub max_file_size: usize,
-    inner: Map,
+    pub file_control: Option<FileControl>,
+    pub default_keep_for: Duration,
+    pub max_keep_for: Duration,
+    inner: Map<T>,
     pub pool: ArcExecutor,
+    /// The backend a `disk_sink` field writes its bytes through.
+    ///
+    /// Swappable at runtime (e.g. to plug an in-memory backend in tests, or an object-store
+    /// backend in production) without rebuilding the `Form` or any `Field::file` it declares -
+    /// see `backend`.
+    pub backend: Arc<StorageBackend>,
+    pub(crate) file_control_applier: Option<fn(T, Option<SystemTime>, bool) -> T>,
 }
 
-impl Form {
+// Hand-written: none of `Form<T>`'s fields need `T: Clone` to clone themselves (`inner` clones
+// unconditionally, `file_control_applier` is a bare `fn` pointer), so this avoids the `T: Clone`
+// bound `#[derive(Clone)]` would add.
+impl<T> Clone for Form<T> {
+    fn clone(&self) -> Self {
+        Form {
+            max_fields: self.max_fields,
+            max_field_size: self.max_field_size,
+            max_files: self.max_files,
+            max_file_size: self.max_file_size,
+            file_control: self.file_control.clone(),
+            default_keep_for: self.default_keep_for,
+            max_keep_for: self.max_keep_for,
+            inner: self.inner.clone(),
+            pool: self.pool.clone(),
+            backend: self.backend.clone(),
+            file_control_applier: self.file_control_applier,
+        }
+    }
+}
+
+impl<T> Form<T> {
     /// Create a new form
     ///
     /// This also creates a new `CpuPool` to be used to stream files onto the filesystem. If you
@@ -460,6 +844,49 @@ impl Form {
         self
     }
 
+    /// Recognize `keep_for`/`delete_on_download` control fields that configure a stored file's
+    /// retention instead of becoming ordinary `Value` entries.
+    ///
+    /// `keep_for` is parsed as a human-readable duration (e.g. `"24h"`, `"7d"`) and clamped to
+    /// `max_keep_for`; `delete_on_download` is parsed as a boolean. Either may be omitted from
+    /// the request, in which case `default_keep_for`/`false` is used.
+    ///
+    /// Only available when `T: FileMetadata`, since applying the parsed retention values means
+    /// calling `T::with_control`. Forms over a `FileSink` output that doesn't implement
+    /// `FileMetadata` simply can't declare a `FileControl`.
+    pub fn file_control(mut self, control: FileControl) -> Self
+    where
+        T: FileMetadata,
+    {
+        self.file_control = Some(control);
+        self.file_control_applier = Some(|file, expires_at, delete_on_download| {
+            file.with_control(expires_at, delete_on_download)
+        });
+
+        self
+    }
+
+    /// Set how long an upload is kept when no `keep_for` control field is submitted.
+    ///
+    /// Accepts a human-readable duration like `"24h"` or `"30m"`. Panics if `duration` can't be
+    /// parsed, since this is meant to be called with a literal at form-construction time.
+    pub fn default_keep_for(mut self, duration: &str) -> Self {
+        self.default_keep_for = parse_duration(duration).expect("Invalid duration");
+
+        self
+    }
+
+    /// Cap how long an upload can be kept, regardless of what a submitted `keep_for` control
+    /// field requests.
+    ///
+    /// Accepts a human-readable duration like `"24h"` or `"30d"`. Panics if `duration` can't be
+    /// parsed, since this is meant to be called with a literal at form-construction time.
+    pub fn max_keep_for(mut self, duration: &str) -> Self {
+        self.max_keep_for = parse_duration(duration).expect("Invalid duration");
+
+        self
+    }
+
     /// Create a new form with a given executor
     ///
     /// This executor is used to stream files onto the filesystem.
@@ -472,23 +899,84 @@ impl Form {
             max_field_size: 10_000,
             max_files: 20,
             max_file_size: 10_000_000,
+            file_control: None,
+            default_keep_for: Duration::from_secs(24 * 60 * 60),
+            max_keep_for: Duration::from_secs(30 * 24 * 60 * 60),
             inner: Map::new(),
             pool: ArcExecutor::new(executor),
+            backend: Arc::new(FsBackend::new()),
+            file_control_applier: None,
         }
     }
 
-    pub fn field(mut self, name: &str, field: Field) -> Self {
+    /// Replace the backend a `disk_sink` field writes its bytes through.
+    ///
+    /// Lets a caller plug an in-memory backend for tests, or a streaming object-store backend
+    /// for production, while reusing all of this `Form`'s existing field definitions.
+    pub fn backend<B>(mut self, backend: B) -> Self
+    where
+        B: StorageBackend + 'static,
+    {
+        self.backend = Arc::new(backend);
+
+        self
+    }
+
+    pub fn field(mut self, name: &str, field: Field<T>) -> Self {
         self.inner = self.inner.field(name, field);
 
         self
     }
 
-    pub(crate) fn valid_field(&self, name: VecDeque<NamePart>) -> Option<FieldTerminator> {
+    /// Add a `Field`, rejecting a `name` that's already present on this `Form`.
+    pub fn try_field(mut self, name: &str, field: Field<T>) -> Result<Self, FormError> {
+        self.inner = self.inner.try_field(name, field)?;
+
+        Ok(self)
+    }
+
+    /// Validate the full field tree - catching duplicate keys (even ones introduced through the
+    /// non-fallible `field`) and maps with no entries, which can never satisfy `valid_field` -
+    /// and hand back `self` unchanged if it's well-formed.
+    pub fn finalize(self) -> Result<Self, FormError> {
+        self.inner.validate()?;
+
+        Ok(self)
+    }
+
+    /// If this `Form` declared a `FileControl`, and `name` names one of its fields, return
+    /// which one.
+    pub(crate) fn control_kind(&self, name: &[NamePart]) -> Option<ControlKind> {
+        let control = match self.file_control {
+            Some(ref control) => control,
+            None => return None,
+        };
+
+        if name.len() != 1 {
+            return None;
+        }
+
+        match name[0] {
+            NamePart::Map(ref key) if key == &control.keep_for => Some(ControlKind::KeepFor),
+            NamePart::Map(ref key) if key == &control.delete_on_download => {
+                Some(ControlKind::DeleteOnDownload)
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn valid_field(&self, name: VecDeque<NamePart>) -> Option<FieldTerminator<T>> {
+        let parts: Vec<NamePart> = name.iter().cloned().collect();
+
+        if let Some(kind) = self.control_kind(&parts) {
+            return Some(FieldTerminator::Control(kind));
+        }
+
         self.inner.valid_field(name.clone())
     }
 }
 
-impl fmt::Debug for Form {
+impl<T> fmt::Debug for Form<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Form({:?})", self.inner)
     }
@@ -540,8 +1028,9 @@ impl ContentDisposition {
     }
 }
 
+/// One segment of a field's bracketed name, e.g. `Map("files")` then `Array` for `files[]`.
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum NamePart {
+pub enum NamePart {
     Map(String),
     Array,
 }
@@ -555,38 +1044,80 @@ impl NamePart {
     }
 }
 
-#[derive(Clone)]
-pub(crate) enum FieldTerminator {
-    File(Arc<FilenameGenerator>),
-    Bytes,
-    Int,
-    Float,
-    Text,
+/// Which control field a `FieldTerminator::Control` corresponds to.
+///
+/// See `Form::file_control`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ControlKind {
+    KeepFor,
+    DeleteOnDownload,
 }
 
-impl fmt::Debug for FieldTerminator {
+pub(crate) enum FieldTerminator<T = SavedFile> {
+    Control(ControlKind),
+    File(FileSpec<T>),
+    Bytes(BytesSpec),
+    Int(Option<u64>),
+    Float(Option<u64>),
+    Text(Option<u64>),
+}
+
+// Hand-written so cloning a `FieldTerminator<T>` doesn't require `T: Clone` - `FileSpec<T>`
+// already clones unconditionally, and every other variant is `T`-free.
+impl<T> Clone for FieldTerminator<T> {
+    fn clone(&self) -> Self {
+        match *self {
+            FieldTerminator::Control(kind) => FieldTerminator::Control(kind),
+            FieldTerminator::File(ref spec) => FieldTerminator::File(spec.clone()),
+            FieldTerminator::Bytes(ref spec) => FieldTerminator::Bytes(spec.clone()),
+            FieldTerminator::Int(max_size) => FieldTerminator::Int(max_size),
+            FieldTerminator::Float(max_size) => FieldTerminator::Float(max_size),
+            FieldTerminator::Text(max_size) => FieldTerminator::Text(max_size),
+        }
+    }
+}
+
+impl<T> fmt::Debug for FieldTerminator<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            FieldTerminator::File(_) => write!(f, "File(filename_generator)"),
-            FieldTerminator::Bytes => write!(f, "Bytes"),
-            FieldTerminator::Int => write!(f, "Int"),
-            FieldTerminator::Float => write!(f, "Float"),
-            FieldTerminator::Text => write!(f, "Text"),
+            FieldTerminator::Control(kind) => write!(f, "Control({:?})", kind),
+            FieldTerminator::File(_) => write!(f, "File(sink)"),
+            FieldTerminator::Bytes(_) => write!(f, "Bytes"),
+            FieldTerminator::Int(_) => write!(f, "Int"),
+            FieldTerminator::Float(_) => write!(f, "Float"),
+            FieldTerminator::Text(_) => write!(f, "Text"),
         }
     }
 }
 
-pub(crate) type MultipartHash = (Vec<NamePart>, MultipartContent);
-pub(crate) type MultipartForm = Vec<MultipartHash>;
+pub(crate) type MultipartHash<T> = (Vec<NamePart>, MultipartContent<T>);
+pub(crate) type MultipartForm<T> = Vec<MultipartHash<T>>;
 
-#[derive(Clone, Debug, PartialEq)]
-pub(crate) enum MultipartContent {
-    File {
-        filename: String,
-        stored_as: PathBuf,
-    },
+/// The result of handling a single field of a multipart form.
+///
+/// A `FileSink` produces the `T` wrapped by `File`, once it has finished consuming the field's
+/// byte stream. If the owning `Form` declared a `FileControl`, that `T` is also given a chance
+/// to record the resolved retention metadata via `FileMetadata::with_control`.
+#[derive(Clone, PartialEq)]
+pub enum MultipartContent<T = SavedFile> {
+    File(T),
     Bytes(Bytes),
     Text(String),
     Int(i64),
     Float(f64),
 }
+
+// Hand-written, like `Field`/`FieldTerminator`'s `Debug` impls, so formatting a
+// `MultipartContent<T>` never requires `T: Debug` - the `File` variant's payload is masked
+// instead of formatted.
+impl<T> fmt::Debug for MultipartContent<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MultipartContent::File(_) => write!(f, "File(_)"),
+            MultipartContent::Bytes(ref b) => write!(f, "Bytes({:?})", b),
+            MultipartContent::Text(ref s) => write!(f, "Text({:?})", s),
+            MultipartContent::Int(i) => write!(f, "Int({:?})", i),
+            MultipartContent::Float(fl) => write!(f, "Float({:?})", fl),
+        }
+    }
+}
@@ -17,21 +17,114 @@
  * along with Actix Form Data.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::{collections::HashMap, fs::DirBuilder, os::unix::fs::DirBuilderExt, path::Path,
-          sync::{Arc, atomic::{AtomicUsize, Ordering}}};
+use std::{collections::HashMap, sync::{Arc, atomic::{AtomicUsize, Ordering}}, time::SystemTime};
 
 use actix_web::{multipart, error::PayloadError};
 use bytes::{Bytes, BytesMut};
-use futures::{Future, Stream, future::{lazy, result, Either, Executor}, sync::oneshot};
-use futures_fs::FsPool;
+use futures::{Future, Poll, Stream, future::{loop_fn, result, Either, Loop}, stream};
 use http::header::CONTENT_DISPOSITION;
+use tracing::Span;
 
+use duration::parse_duration;
 use error::Error;
-use super::FilenameGenerator;
-use types::{self, ContentDisposition, MultipartContent, MultipartForm, MultipartHash, NamePart,
-            Value};
+use types::{self, ContentDisposition, FileSpec, MultipartContent, MultipartForm, MultipartHash,
+            NamePart, Value};
+
+/// Enters `span` every time `inner` is polled, so logs and errors produced while draining a
+/// field or file nest under its span instead of just the form's.
+struct Instrumented<Fut> {
+    inner: Fut,
+    span: Span,
+}
+
+impl<Fut> Future for Instrumented<Fut>
+where
+    Fut: Future,
+{
+    type Item = Fut::Item;
+    type Error = Fut::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let _enter = self.span.enter();
+        self.inner.poll()
+    }
+}
+
+trait InstrumentExt: Future + Sized {
+    fn in_span(self, span: Span) -> Instrumented<Self> {
+        Instrumented { inner: self, span }
+    }
+}
+
+impl<Fut> InstrumentExt for Fut
+where
+    Fut: Future,
+{
+}
+
+/// Number of leading bytes of a file's stream buffered for content-sniffing.
+const SNIFF_LEN: usize = 512;
+
+/// Guess a file's MIME type from its leading bytes, independent of any client-provided header.
+fn sniff_content_type(bytes: &[u8]) -> mime::Mime {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        mime::IMAGE_PNG
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        mime::IMAGE_JPEG
+    } else if bytes.starts_with(b"GIF8") {
+        "image/gif".parse().unwrap()
+    } else if bytes.starts_with(b"%PDF") {
+        "application/pdf".parse().unwrap()
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        "application/zip".parse().unwrap()
+    } else {
+        mime::APPLICATION_OCTET_STREAM
+    }
+}
+
+/// Buffer the leading bytes of `stream` (up to `SNIFF_LEN`), sniff a content type from them, and
+/// hand back a stream that replays those buffered bytes followed by the rest of `stream`.
+///
+/// A single `into_future()` isn't enough here: a chunked-transfer client may hand us only a
+/// handful of bytes per chunk, well short of `SNIFF_LEN`, so this loops pulling more chunks
+/// until the buffer reaches `SNIFF_LEN` or the stream runs dry.
+fn sniff<S>(stream: S) -> impl Future<Item = (mime::Mime, types::FileStream), Error = Error>
+where
+    S: Stream<Item = Bytes, Error = Error> + 'static,
+{
+    loop_fn((stream, BytesMut::new()), |(stream, mut buf)| {
+        stream.into_future().map_err(|(e, _)| e).map(move |(chunk, rest)| {
+            match chunk {
+                Some(bytes) => {
+                    buf.extend_from_slice(&bytes);
+
+                    if buf.len() >= SNIFF_LEN {
+                        Loop::Break((buf, rest))
+                    } else {
+                        Loop::Continue((rest, buf))
+                    }
+                }
+                None => Loop::Break((buf, rest)),
+            }
+        })
+    }).map(|(buf, rest)| {
+        let buf = buf.freeze();
+        let detected = sniff_content_type(buf.get(..SNIFF_LEN).unwrap_or(&buf));
+
+        let prefix: types::FileStream = if buf.is_empty() {
+            Box::new(stream::empty())
+        } else {
+            Box::new(stream::once(Ok(buf)))
+        };
+
+        (detected, Box::new(prefix.chain(rest)) as types::FileStream)
+    })
+}
 
-fn consolidate(mf: MultipartForm) -> Value {
+fn consolidate<T>(mf: MultipartForm<T>) -> Value<T>
+where
+    T: Clone,
+{
     mf.into_iter().fold(
         Value::Map(HashMap::new()),
         |mut acc, (mut nameparts, content)| {
@@ -57,6 +150,22 @@ fn consolidate(mf: MultipartForm) -> Value {
     )
 }
 
+/// Render a parsed field name back into its bracketed form, for error messages.
+fn format_name(name: &[NamePart]) -> String {
+    name.iter().enumerate().fold(String::new(), |mut acc, (i, part)| {
+        match *part {
+            NamePart::Map(ref key) if i == 0 => acc.push_str(key),
+            NamePart::Map(ref key) => {
+                acc.push('[');
+                acc.push_str(key);
+                acc.push(']');
+            }
+            NamePart::Array => acc.push_str("[]"),
+        }
+        acc
+    })
+}
+
 fn parse_multipart_name(name: String) -> Result<Vec<NamePart>, Error> {
     name.split('[')
         .map(|part| {
@@ -127,174 +236,178 @@ where
         }))
 }
 
-fn handle_file_upload<S>(
+fn handle_file_upload<S, T>(
     field: multipart::Field<S>,
-    gen: Arc<FilenameGenerator>,
+    spec: FileSpec<T>,
+    name: Vec<NamePart>,
     filename: Option<String>,
-    form: types::Form,
-) -> impl Future<Item = MultipartContent, Error = Error>
+    form: types::Form<T>,
+    span: Span,
+) -> impl Future<Item = MultipartContent<T>, Error = Error>
 where
-    S: Stream<Item = Bytes, Error = PayloadError>,
+    S: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    T: 'static,
 {
-    let filename = match filename {
-        Some(filename) => filename,
-        None => return Either::B(result(Err(Error::Filename))),
-    };
-
-    let path: &Path = filename.as_ref();
-    let filename = path.file_name().and_then(|filename| filename.to_str());
-
-    let filename = if let Some(filename) = filename {
-        filename.to_owned()
-    } else {
-        return Either::B(result(Err(Error::Filename)));
-    };
-
-    let stored_as = match gen.next_filename(field.content_type()) {
-        Some(file_path) => file_path,
-        None => return Either::B(result(Err(Error::GenFilename))),
-    };
-
-    let mut stored_dir = stored_as.clone();
-    stored_dir.pop();
-
-    let (tx, rx) = oneshot::channel();
-
-    match form.pool.execute(Box::new(lazy(move || {
-        let res = DirBuilder::new()
-            .recursive(true)
-            .mode(0o755)
-            .create(stored_dir.clone())
-            .map_err(|_| Error::MkDir);
-
-        tx.send(res).map_err(|_| ())
-    }))) {
-        | Ok(_) => (),
-        Err(_) => return Either::B(result(Err(Error::MkDir))),
-    };
+    let file_span = info_span!(parent: &span, "file", filename = ?filename);
+    let sink_span = file_span.clone();
 
+    let max_size = spec.max_size.unwrap_or(u64::max_value()).min(form.max_file_size as u64);
     let counter = Arc::new(AtomicUsize::new(0));
+    let error_name = format_name(&name);
+    let backend = form.backend.clone();
 
-    Either::A(rx.then(|res| match res {
-        Ok(res) => res,
-        Err(_) => Err(Error::MkDir),
-    }).and_then(move |_| {
-        let write =
-            FsPool::from_executor(form.pool.clone()).write(stored_as.clone(), Default::default());
-        field
-            .map_err(Error::Multipart)
-            .and_then(move |bytes| {
-                let size = counter.fetch_add(bytes.len(), Ordering::Relaxed) + bytes.len();
+    let stream: types::FileStream = Box::new(field.map_err(Error::Multipart).and_then(
+        move |bytes| {
+            let size = counter.fetch_add(bytes.len(), Ordering::Relaxed) + bytes.len();
 
-                if size > form.max_file_size {
-                    Err(Error::FileSize)
-                } else {
-                    Ok(bytes)
-                }
-            })
-            .forward(write)
-            .map(move |_| MultipartContent::File {
-                filename,
-                stored_as,
-            })
-    }))
+            if size as u64 > max_size {
+                Err(Error::FileSize(error_name.clone()))
+            } else {
+                Ok(bytes)
+            }
+        },
+    ));
+
+    sniff(stream)
+        .and_then(move |(content_type, stream)| {
+            if spec.accepts(&content_type) {
+                Either::A(
+                    spec.sink
+                        .call(name, filename, content_type, stream, backend, sink_span)
+                        .map(MultipartContent::File),
+                )
+            } else {
+                Either::B(result(Err(Error::ContentType)))
+            }
+        })
+        .in_span(file_span)
 }
 
-fn handle_form_data<S>(
+fn handle_form_data<S, T>(
     field: multipart::Field<S>,
-    term: types::FieldTerminator,
-    form: types::Form,
-) -> impl Future<Item = MultipartContent, Error = Error>
+    term: types::FieldTerminator<T>,
+    name: Vec<NamePart>,
+    form: types::Form<T>,
+    span: Span,
+) -> impl Future<Item = MultipartContent<T>, Error = Error>
 where
     S: Stream<Item = Bytes, Error = PayloadError>,
 {
     trace!("In handle_form_data, term: {:?}", term);
     let term2 = term.clone();
 
-    field
-        .from_err()
-        .fold(BytesMut::new(), move |mut acc, bytes| {
-            if acc.len() + bytes.len() < form.max_field_size {
-                acc.extend(bytes);
-                Ok(acc)
-            } else {
-                Err(Error::FieldSize)
-            }
-        })
-        .and_then(move |bytes| match term {
-            types::FieldTerminator::Bytes => Ok(MultipartContent::Bytes(bytes.freeze())),
-            _ => String::from_utf8(bytes.to_vec())
-                .map_err(Error::ParseField)
-                .map(MultipartContent::Text),
-        })
-        .and_then(move |content| {
-            trace!("Matching: {:?}", content);
-            match content {
-                types::MultipartContent::Text(string) => match term2 {
-                    types::FieldTerminator::File(_) => Err(Error::FieldType),
-                    types::FieldTerminator::Bytes => Err(Error::FieldType),
-                    types::FieldTerminator::Float => string
-                        .parse::<f64>()
-                        .map(MultipartContent::Float)
-                        .map_err(Error::ParseFloat),
-                    types::FieldTerminator::Int => string
-                        .parse::<i64>()
-                        .map(MultipartContent::Int)
-                        .map_err(Error::ParseInt),
-                    types::FieldTerminator::Text => Ok(MultipartContent::Text(string)),
-                },
-                b @ types::MultipartContent::Bytes(_) => Ok(b),
-                _ => Err(Error::FieldType),
+    let field_max_size = match term {
+        types::FieldTerminator::Bytes(ref spec) => {
+            if !spec.accepts(field.content_type()) {
+                return Either::A(result(Err(Error::ContentType))).in_span(span);
             }
-        })
+
+            spec.max_size
+        }
+        types::FieldTerminator::Int(max_size)
+        | types::FieldTerminator::Float(max_size)
+        | types::FieldTerminator::Text(max_size) => max_size,
+        types::FieldTerminator::File(_) | types::FieldTerminator::Control(_) => None,
+    };
+    let max_size = field_max_size.unwrap_or(u64::max_value()).min(form.max_field_size as u64);
+    let error_name = format_name(&name);
+
+    Either::B(
+        field
+            .from_err()
+            .fold(BytesMut::new(), move |mut acc, bytes| {
+                if ((acc.len() + bytes.len()) as u64) < max_size {
+                    acc.extend(bytes);
+                    Ok(acc)
+                } else {
+                    Err(Error::FieldSize(error_name.clone()))
+                }
+            })
+            .and_then(move |bytes| match term {
+                types::FieldTerminator::Bytes(_) => Ok(MultipartContent::Bytes(bytes.freeze())),
+                _ => String::from_utf8(bytes.to_vec())
+                    .map_err(Error::ParseField)
+                    .map(MultipartContent::Text),
+            })
+            .and_then(move |content| {
+                trace!("Matching: {:?}", content);
+                match content {
+                    types::MultipartContent::Text(string) => match term2 {
+                        types::FieldTerminator::File(_) => Err(Error::FieldType),
+                        types::FieldTerminator::Bytes(_) => Err(Error::FieldType),
+                        types::FieldTerminator::Float(_) => string
+                            .parse::<f64>()
+                            .map(MultipartContent::Float)
+                            .map_err(Error::ParseFloat),
+                        types::FieldTerminator::Int(_) => string
+                            .parse::<i64>()
+                            .map(MultipartContent::Int)
+                            .map_err(Error::ParseInt),
+                        types::FieldTerminator::Text(_) | types::FieldTerminator::Control(_) => {
+                            Ok(MultipartContent::Text(string))
+                        }
+                    },
+                    b @ types::MultipartContent::Bytes(_) => Ok(b),
+                    _ => Err(Error::FieldType),
+                }
+            }),
+    ).in_span(span)
 }
 
-fn handle_stream_field<S>(
+fn handle_stream_field<S, T>(
     field: multipart::Field<S>,
-    form: types::Form,
-) -> impl Future<Item = MultipartHash, Error = Error>
+    form: types::Form<T>,
+    span: Span,
+) -> impl Future<Item = MultipartHash<T>, Error = Error>
 where
-    S: Stream<Item = Bytes, Error = PayloadError>,
+    S: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    T: 'static,
 {
     let content_disposition = match parse_content_disposition(&field) {
         Ok(cd) => cd,
-        Err(e) => return Either::B(result(Err(e))),
+        Err(e) => return Either::B(result(Err(e)).in_span(span)),
     };
 
     let name = match content_disposition.name {
         Some(name) => name,
-        None => return Either::B(result(Err(Error::Field))),
+        None => return Either::B(result(Err(Error::Field)).in_span(span)),
     };
 
     let name = match parse_multipart_name(name) {
         Ok(name) => name,
-        Err(e) => return Either::B(result(Err(e))),
+        Err(e) => return Either::B(result(Err(e)).in_span(span)),
     };
 
+    let field_span = info_span!(parent: &span, "field", name = %format_name(&name));
+
     let term = match form.valid_field(name.iter().cloned().collect()) {
         Some(term) => term,
-        None => return Either::B(result(Err(Error::FieldType))),
+        None => return Either::B(result(Err(Error::FieldType)).in_span(field_span)),
     };
 
     let fut = match term {
-        types::FieldTerminator::File(gen) => Either::A(handle_file_upload(
+        types::FieldTerminator::File(spec) => Either::A(handle_file_upload(
             field,
-            gen,
+            spec,
+            name.clone(),
             content_disposition.filename,
             form,
+            field_span.clone(),
         )),
-        term => Either::B(handle_form_data(field, term, form)),
+        term => Either::B(handle_form_data(field, term, name.clone(), form, field_span.clone())),
     };
 
-    Either::A(fut.map(|content| (name, content)))
+    Either::A(fut.map(|content| (name, content)).in_span(field_span))
 }
 
-fn handle_stream<S>(
+fn handle_stream<S, T>(
     m: multipart::Multipart<S>,
-    form: types::Form,
-) -> Box<Stream<Item = MultipartHash, Error = Error>>
+    form: types::Form<T>,
+    span: Span,
+) -> Box<Stream<Item = MultipartHash<T>, Error = Error>>
 where
     S: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    T: Clone + 'static,
 {
     Box::new(
         m.map_err(Error::from)
@@ -302,68 +415,290 @@ where
                 multipart::MultipartItem::Field(field) => {
                     info!("Field: {:?}", field);
                     Box::new(
-                        handle_stream_field(field, form.clone())
+                        handle_stream_field(field, form.clone(), span.clone())
                             .map(From::from)
                             .into_stream(),
-                    ) as Box<Stream<Item = MultipartHash, Error = Error>>
+                    ) as Box<Stream<Item = MultipartHash<T>, Error = Error>>
                 }
                 multipart::MultipartItem::Nested(m) => {
                     info!("Nested");
-                    Box::new(handle_stream(m, form.clone()))
-                        as Box<Stream<Item = MultipartHash, Error = Error>>
+                    Box::new(handle_stream(m, form.clone(), span.clone()))
+                        as Box<Stream<Item = MultipartHash<T>, Error = Error>>
                 }
             })
             .flatten(),
     )
 }
 
+/// Parse a `delete_on_download` control field's raw text into a bool.
+fn parse_bool(input: &str) -> Result<bool, Error> {
+    match input.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" => Ok(false),
+        _ => Err(Error::ParseBool(input.to_owned())),
+    }
+}
+
+/// If `form` declared a `FileControl`, resolve the submitted `keep_for`/`delete_on_download`
+/// control values and stamp every `File` entry of `multipart_form` with them.
+///
+/// Control fields are consumed before `consolidate` ever sees them, so they never show up in
+/// the resulting `Value` tree.
+fn apply_file_control<T>(
+    form: &types::Form<T>,
+    multipart_form: MultipartForm<T>,
+    keep_for: Option<String>,
+    delete_on_download: Option<String>,
+) -> Result<MultipartForm<T>, Error> {
+    let applier = match form.file_control_applier {
+        Some(applier) => applier,
+        None => return Ok(multipart_form),
+    };
+
+    let keep_for = match keep_for {
+        Some(raw) => parse_duration(&raw)?,
+        None => form.default_keep_for,
+    }.min(form.max_keep_for);
+
+    let delete_on_download = match delete_on_download {
+        Some(raw) => parse_bool(&raw)?,
+        None => false,
+    };
+
+    let expires_at = SystemTime::now() + keep_for;
+
+    Ok(multipart_form
+        .into_iter()
+        .map(|(name, content)| {
+            let content = match content {
+                MultipartContent::File(file) => {
+                    MultipartContent::File(applier(file, Some(expires_at), delete_on_download))
+                }
+                content => content,
+            };
+
+            (name, content)
+        })
+        .collect())
+}
+
 /// Handle multipart streams from Actix Web
-pub fn handle_multipart<S>(
+pub fn handle_multipart<S, T>(
     m: multipart::Multipart<S>,
-    form: types::Form,
-) -> impl Future<Item = Value, Error = Error>
+    form: types::Form<T>,
+) -> impl Future<Item = Value<T>, Error = Error>
 where
     S: Stream<Item = Bytes, Error = PayloadError> + 'static,
+    T: Clone + 'static,
 {
-    handle_stream(m, form.clone())
+    let control_form = form.clone();
+    let span = info_span!("multipart-form");
+
+    handle_stream(m, form.clone(), span.clone())
         .fold(
-            (Vec::new(), 0, 0),
-            move |(mut acc, file_count, field_count), (name, content)| match content {
-                MultipartContent::File {
-                    filename,
-                    stored_as,
-                } => {
-                    let file_count = file_count + 1;
-
-                    if file_count < form.max_files {
-                        acc.push((
-                            name,
-                            MultipartContent::File {
-                                filename,
-                                stored_as,
-                            },
-                        ));
-
-                        Ok((acc, file_count, field_count))
-                    } else {
-                        Err(Error::FileCount)
+            (Vec::new(), 0, 0, None, None),
+            move |(mut acc, file_count, field_count, keep_for, delete_on_download),
+                  (name, content)| {
+                match form.control_kind(&name) {
+                    Some(types::ControlKind::KeepFor) => {
+                        let keep_for = match content {
+                            MultipartContent::Text(string) => Some(string),
+                            _ => keep_for,
+                        };
+
+                        return Ok((acc, file_count, field_count, keep_for, delete_on_download));
                     }
+                    Some(types::ControlKind::DeleteOnDownload) => {
+                        let delete_on_download = match content {
+                            MultipartContent::Text(string) => Some(string),
+                            _ => delete_on_download,
+                        };
+
+                        return Ok((acc, file_count, field_count, keep_for, delete_on_download));
+                    }
+                    None => (),
                 }
-                b @ MultipartContent::Bytes(_)
-                | b @ MultipartContent::Text(_)
-                | b @ MultipartContent::Float(_)
-                | b @ MultipartContent::Int(_) => {
-                    let field_count = field_count + 1;
 
-                    if field_count < form.max_fields {
-                        acc.push((name, b));
+                match content {
+                    f @ MultipartContent::File(_) => {
+                        let file_count = file_count + 1;
 
-                        Ok((acc, file_count, field_count))
-                    } else {
-                        Err(Error::FieldCount)
+                        if file_count < form.max_files {
+                            acc.push((name, f));
+
+                            Ok((acc, file_count, field_count, keep_for, delete_on_download))
+                        } else {
+                            Err(Error::FileCount)
+                        }
+                    }
+                    b @ MultipartContent::Bytes(_)
+                    | b @ MultipartContent::Text(_)
+                    | b @ MultipartContent::Float(_)
+                    | b @ MultipartContent::Int(_) => {
+                        let field_count = field_count + 1;
+
+                        if field_count < form.max_fields {
+                            acc.push((name, b));
+
+                            Ok((acc, file_count, field_count, keep_for, delete_on_download))
+                        } else {
+                            Err(Error::FieldCount)
+                        }
                     }
                 }
             },
         )
-        .map(|(multipart_form, _, _)| consolidate(multipart_form))
+        .and_then(move |(multipart_form, _, _, keep_for, delete_on_download)| {
+            result(apply_file_control(
+                &control_form,
+                multipart_form,
+                keep_for,
+                delete_on_download,
+            ))
+        })
+        .map(consolidate)
+        .in_span(span)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use types::{FileControl, Form, NamePart, SavedFile};
+
+    use super::*;
+
+    #[test]
+    fn sniffs_a_known_magic_number_across_many_small_chunks() {
+        let png = b"\x89PNG\r\n\x1a\n rest of the file";
+        let chunks: Vec<Bytes> = png.chunks(3).map(|c| Bytes::from(c.to_vec())).collect();
+        let stream = stream::iter_ok::<_, Error>(chunks);
+
+        let (mime, out) = sniff(stream).wait().unwrap();
+
+        assert_eq!(mime, mime::IMAGE_PNG);
+        assert_eq!(out.collect().wait().unwrap().concat(), png.to_vec());
+    }
+
+    #[test]
+    fn sniffs_unrecognized_bytes_as_octet_stream() {
+        let stream = stream::iter_ok::<_, Error>(vec![Bytes::from_static(b"just some text")]);
+
+        let (mime, _) = sniff(stream).wait().unwrap();
+
+        assert_eq!(mime, mime::APPLICATION_OCTET_STREAM);
+    }
+
+    #[test]
+    fn sniff_content_type_recognizes_each_magic_number() {
+        assert_eq!(sniff_content_type(b"\x89PNG\r\n\x1a\n"), mime::IMAGE_PNG);
+        assert_eq!(sniff_content_type(&[0xFF, 0xD8, 0xFF]), mime::IMAGE_JPEG);
+        assert_eq!(sniff_content_type(b"GIF8"), "image/gif".parse().unwrap());
+        assert_eq!(
+            sniff_content_type(b"%PDF"),
+            "application/pdf".parse().unwrap()
+        );
+        assert_eq!(
+            sniff_content_type(b"PK\x03\x04"),
+            "application/zip".parse().unwrap()
+        );
+        assert_eq!(sniff_content_type(b"whatever"), mime::APPLICATION_OCTET_STREAM);
+    }
+
+    #[test]
+    fn formats_nested_and_array_names() {
+        let name = vec![
+            NamePart::Map("outer".to_owned()),
+            NamePart::Map("inner".to_owned()),
+            NamePart::Array,
+        ];
+
+        assert_eq!(format_name(&name), "outer[inner][]");
+    }
+
+    #[test]
+    fn parses_bracketed_names_into_parts() {
+        let name = parse_multipart_name("outer[inner][]".to_owned()).unwrap();
+
+        assert_eq!(
+            name,
+            vec![
+                NamePart::Map("outer".to_owned()),
+                NamePart::Map("inner".to_owned()),
+                NamePart::Array,
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_name_starting_with_an_array_part() {
+        assert!(parse_multipart_name("[]".to_owned()).is_err());
+    }
+
+    #[test]
+    fn parse_bool_recognizes_common_spellings() {
+        assert_eq!(parse_bool("true").unwrap(), true);
+        assert_eq!(parse_bool("Yes").unwrap(), true);
+        assert_eq!(parse_bool("0").unwrap(), false);
+        assert_eq!(parse_bool("off").unwrap(), false);
+        assert!(parse_bool("maybe").is_err());
+    }
+
+    fn file(name: &str) -> SavedFile {
+        SavedFile {
+            filename: name.to_owned(),
+            stored_as: name.into(),
+            expires_at: None,
+            delete_on_download: false,
+        }
+    }
+
+    #[test]
+    fn apply_file_control_is_a_no_op_without_a_declared_file_control() {
+        let form = Form::<SavedFile>::new();
+        let multipart_form = vec![
+            (vec![NamePart::Map("f".to_owned())], MultipartContent::File(file("a"))),
+        ];
+
+        let out = apply_file_control(&form, multipart_form.clone(), None, None).unwrap();
+
+        assert_eq!(out, multipart_form);
+    }
+
+    #[test]
+    fn apply_file_control_stamps_expiry_and_defaults_delete_on_download() {
+        let form = Form::<SavedFile>::new().file_control(FileControl {
+            keep_for: "keep_for".to_owned(),
+            delete_on_download: "delete_on_download".to_owned(),
+        });
+        let multipart_form = vec![
+            (vec![NamePart::Map("f".to_owned())], MultipartContent::File(file("a"))),
+        ];
+
+        let out = apply_file_control(&form, multipart_form, Some("1h".to_owned()), None).unwrap();
+
+        match &out[0].1 {
+            MultipartContent::File(saved) => {
+                assert!(saved.expires_at.is_some());
+                assert_eq!(saved.delete_on_download, false);
+            }
+            _ => panic!("expected a File entry"),
+        }
+    }
+
+    #[test]
+    fn apply_file_control_rejects_an_unparseable_keep_for() {
+        let form = Form::<SavedFile>::new().file_control(FileControl {
+            keep_for: "keep_for".to_owned(),
+            delete_on_download: "delete_on_download".to_owned(),
+        });
+        let multipart_form = vec![
+            (vec![NamePart::Map("f".to_owned())], MultipartContent::File(file("a"))),
+        ];
+
+        let result =
+            apply_file_control(&form, multipart_form, Some("not-a-duration".to_owned()), None);
+
+        assert!(result.is_err());
+    }
 }